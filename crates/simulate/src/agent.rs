@@ -0,0 +1,22 @@
+#![warn(missing_docs)]
+//! This module contains the `Agent` type, a lightweight handle to an account that can be used to
+//! send transactions into a [`crate::environment::SimulationManager`].
+
+use revm::primitives::B160;
+
+/// Represents a single account participating in a simulation (e.g. a deployer, a trader, or a
+/// liquidity provider).
+#[derive(Debug, Clone)]
+pub struct Agent {
+    /// The address this agent transacts from.
+    pub address: B160,
+    /// The next nonce to use when this agent sends a transaction.
+    pub nonce: u64,
+}
+
+impl Agent {
+    /// Creates a new agent for the given address, starting at nonce zero.
+    pub fn new(address: B160) -> Self {
+        Self { address, nonce: 0 }
+    }
+}
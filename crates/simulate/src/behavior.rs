@@ -0,0 +1,472 @@
+#![warn(missing_docs)]
+//! This module contains the `Behavior` trait and the built-in behaviors (`Deployer`,
+//! `TokenAdmin`, `PoolAdmin`) that replace hand-rolled deploy/mint/swap sequences with
+//! declarative, serializable simulation configs.
+
+use std::ops::ControlFlow;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bindings;
+use ethers::prelude::BaseContract;
+use ethers::types::{Address, U256};
+use revm::db::{DatabaseRef, EmptyDB};
+use revm::primitives::{ruint::Uint, Log, B160};
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+
+use crate::environment::{recast_address, SimulationContract, SimulationManager};
+use crate::exchange::Cfmm;
+use crate::token::TokenAmount;
+
+/// A single step in a behavior's lifecycle: a raw, undecoded log emitted by a contract in the
+/// simulation, dispatched to every behavior that is listening.
+pub type Event = Log;
+
+/// A shared handle to the `SimulationManager` a simulation's behaviors run against, cloneable so
+/// every `Behavior` can hold its own client into the same running simulation. Generic over the
+/// manager's database so the same `Behavior` impls work against both the default, purely
+/// in-memory `SimulationManager` and one built via `SimulationManager::fork`.
+pub type ManagerClient<DB = EmptyDB> = Arc<Mutex<SimulationManager<DB>>>;
+
+/// A composable unit of simulation logic that can deploy contracts, hold state, and react to
+/// events emitted elsewhere in the simulation.
+///
+/// Implementors hold a client handle into the `SimulationManager` they were started with and are
+/// driven by the simulation's event loop: `startup` runs once, then `process` runs once per
+/// event until it returns [`ControlFlow::Break`].
+#[async_trait]
+pub trait Behavior<C>: Send {
+    /// Runs once when the behavior is added to a running simulation (e.g. to deploy contracts or
+    /// register for events).
+    async fn startup(&mut self, client: C);
+    /// Runs once per event the behavior is subscribed to. Returning
+    /// [`ControlFlow::Break`] deregisters the behavior from further events.
+    async fn process(&mut self, event: Event) -> ControlFlow<()>;
+}
+
+/// Parameters needed to deploy a token pair and a `LiquidExchange` quoting `token_y` in terms of
+/// `token_x`. Deserializable so a simulation can be described as data rather than code.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeployerParams {
+    /// Name/symbol for token `x`.
+    pub token_x: (String, String),
+    /// Name/symbol for token `y`.
+    pub token_y: (String, String),
+    /// Initial price (token `y` per token `x`) the `LiquidExchange` is deployed with.
+    pub initial_price: U256,
+}
+
+/// Deploys the token pair and the `LiquidExchange` contract that trades them.
+pub struct Deployer {
+    /// The parameters this deployer was configured with.
+    pub params: DeployerParams,
+    /// The deployed token `x` contract, set after `startup` runs.
+    pub token_x: Option<SimulationContract>,
+    /// The deployed token `y` contract, set after `startup` runs.
+    pub token_y: Option<SimulationContract>,
+    /// The deployed `LiquidExchange` contract, set after `startup` runs.
+    pub exchange: Option<SimulationContract>,
+}
+
+impl Deployer {
+    /// Creates a new deployer from a parameter struct, typically loaded from a simulation config.
+    pub fn new(params: DeployerParams) -> Self {
+        Self {
+            params,
+            token_x: None,
+            token_y: None,
+            exchange: None,
+        }
+    }
+}
+
+#[async_trait]
+impl<DB: DatabaseRef + Send + 'static> Behavior<ManagerClient<DB>> for Deployer {
+    async fn startup(&mut self, client: ManagerClient<DB>) {
+        let mut manager = client.lock().await;
+
+        let arbiter_token = SimulationContract::new(
+            BaseContract::from(bindings::arbiter_token::ARBITERTOKEN_ABI.clone()),
+            bindings::arbiter_token::ARBITERTOKEN_BYTECODE
+                .clone()
+                .into_iter()
+                .collect(),
+        );
+        let token_x = manager.deploy(&arbiter_token, self.params.token_x.clone());
+        let token_y = manager.deploy(&arbiter_token, self.params.token_y.clone());
+
+        let liquid_exchange = SimulationContract::new(
+            BaseContract::from(bindings::liquid_exchange::LIQUIDEXCHANGE_ABI.clone()),
+            bindings::liquid_exchange::LIQUIDEXCHANGE_BYTECODE
+                .clone()
+                .into_iter()
+                .collect(),
+        );
+        let exchange = manager.deploy(
+            &liquid_exchange,
+            (
+                recast_address(token_x.address.expect("token_x was just deployed")),
+                recast_address(token_y.address.expect("token_y was just deployed")),
+                self.params.initial_price,
+            ),
+        );
+
+        self.token_x = Some(token_x);
+        self.token_y = Some(token_y);
+        self.exchange = Some(exchange);
+    }
+
+    async fn process(&mut self, _event: Event) -> ControlFlow<()> {
+        // The deployer's job is done once `startup` has deployed the pair and the exchange.
+        ControlFlow::Break(())
+    }
+}
+
+/// Owns minting and allowance management for a single token on behalf of the accounts in a
+/// simulation.
+pub struct TokenAdmin {
+    /// The token contract this admin manages.
+    pub token: SimulationContract,
+    /// Pending `(recipient, amount)` mints to perform on `startup`.
+    pub mints: Vec<(B160, U256)>,
+    /// Pending `(spender, amount)` allowance increases, approved from the simulation's admin
+    /// account, to perform on `startup`.
+    pub allowances: Vec<(B160, U256)>,
+}
+
+impl TokenAdmin {
+    /// Creates a new admin for an already-deployed token with no pending mints or allowances.
+    pub fn new(token: SimulationContract) -> Self {
+        Self {
+            token,
+            mints: Vec::new(),
+            allowances: Vec::new(),
+        }
+    }
+
+    /// Queues a mint of `amount` to `recipient`, performed the next time `startup` runs.
+    pub fn mint(mut self, recipient: B160, amount: U256) -> Self {
+        self.mints.push((recipient, amount));
+        self
+    }
+
+    /// Queues an allowance increase of `amount` for `spender`, performed the next time `startup`
+    /// runs.
+    pub fn approve(mut self, spender: B160, amount: U256) -> Self {
+        self.allowances.push((spender, amount));
+        self
+    }
+}
+
+#[async_trait]
+impl<DB: DatabaseRef + Send + 'static> Behavior<ManagerClient<DB>> for TokenAdmin {
+    async fn startup(&mut self, client: ManagerClient<DB>) {
+        let mut manager = client.lock().await;
+
+        for (recipient, amount) in &self.mints {
+            let call_data = self
+                .token
+                .base_contract
+                .encode("mint", (recast_address(*recipient), *amount))
+                .expect("failed to encode `mint`")
+                .into_iter()
+                .collect();
+            manager.call_contract(&self.token, call_data, Uint::from(0));
+        }
+
+        for (spender, amount) in &self.allowances {
+            let call_data = self
+                .token
+                .base_contract
+                .encode("increaseAllowance", (recast_address(*spender), *amount))
+                .expect("failed to encode `increaseAllowance`")
+                .into_iter()
+                .collect();
+            manager.call_contract(&self.token, call_data, Uint::from(0));
+        }
+    }
+
+    async fn process(&mut self, _event: Event) -> ControlFlow<()> {
+        ControlFlow::Break(())
+    }
+}
+
+/// Manages liquidity on behalf of the accounts in a simulation, keeping an in-memory [`Cfmm`]
+/// pool in sync with the deployed `LiquidExchange` it mirrors: `startup` mints each queued
+/// deposit's `dx`/`dy` to the exchange (so it is actually capitalized on-chain) and replays the
+/// same deposit into `pool`, and `process` decodes every `SwapOccured` the exchange emits and
+/// replays it into `pool` too, the same way `ConstantProductPool`'s own doc comment describes
+/// using it "to validate agent behavior against the deployed `LiquidExchange` contract".
+pub struct PoolAdmin {
+    /// The in-memory pool this admin keeps in sync with `exchange`.
+    pub pool: Arc<Mutex<dyn Cfmm + Send>>,
+    /// The deployed token `x` contract backing `exchange`.
+    pub token_x: SimulationContract,
+    /// The deployed token `y` contract backing `exchange`.
+    pub token_y: SimulationContract,
+    /// The deployed `LiquidExchange` this admin manages liquidity for.
+    pub exchange: SimulationContract,
+    /// Pending `(caller, dx, dy)` deposits to perform on `startup`.
+    pub deposits: Vec<(Address, TokenAmount, TokenAmount)>,
+}
+
+impl PoolAdmin {
+    /// Creates a new admin for an already-deployed `exchange` (and the token pair backing it)
+    /// with no pending deposits.
+    pub fn new(
+        pool: Arc<Mutex<dyn Cfmm + Send>>,
+        token_x: SimulationContract,
+        token_y: SimulationContract,
+        exchange: SimulationContract,
+    ) -> Self {
+        Self {
+            pool,
+            token_x,
+            token_y,
+            exchange,
+            deposits: Vec::new(),
+        }
+    }
+
+    /// Queues a `caller` deposit of `dx`/`dy`, performed the next time `startup` runs.
+    pub fn add_liquidity(mut self, caller: Address, dx: TokenAmount, dy: TokenAmount) -> Self {
+        self.deposits.push((caller, dx, dy));
+        self
+    }
+}
+
+#[async_trait]
+impl<DB: DatabaseRef + Send + 'static> Behavior<ManagerClient<DB>> for PoolAdmin {
+    async fn startup(&mut self, client: ManagerClient<DB>) {
+        let mut manager = client.lock().await;
+        let exchange_address = recast_address(self.exchange.address.expect(
+            "PoolAdmin's exchange must already be deployed (e.g. by a `Deployer`) before startup",
+        ));
+        let pool = self.pool.lock().await;
+
+        for (caller, dx, dy) in self.deposits.drain(..) {
+            // Mint the deposit straight to the exchange so it is actually capitalized on-chain,
+            // mirroring the same deposit into `pool`, the in-memory model of that exchange.
+            let call_data = self
+                .token_x
+                .base_contract
+                .encode("mint", (exchange_address, dx.raw))
+                .expect("failed to encode `mint`")
+                .into_iter()
+                .collect();
+            manager.call_contract(&self.token_x, call_data, Uint::from(0));
+
+            let call_data = self
+                .token_y
+                .base_contract
+                .encode("mint", (exchange_address, dy.raw))
+                .expect("failed to encode `mint`")
+                .into_iter()
+                .collect();
+            manager.call_contract(&self.token_y, call_data, Uint::from(0));
+
+            pool.add_liquidity(caller, dx, dy);
+        }
+    }
+
+    async fn process(&mut self, event: Event) -> ControlFlow<()> {
+        // Decimals aren't tracked per-token here, so 18 is assumed — the same fallback
+        // `SimulationManager::token_amount` uses for a token it has no recorded decimals for,
+        // and what every token this simulation deploys via `bindings::arbiter_token` actually is.
+        if let Ok((token_in, amount_in, _token_out, _amount_out)) =
+            decode_swap_occured(&self.exchange, event)
+        {
+            let pool = self.pool.lock().await;
+            pool.swap(token_in, TokenAmount::new(amount_in, 18));
+        }
+        ControlFlow::Continue(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exchange::ConstantProductPool;
+
+    #[test]
+    fn test_token_admin_builder_queues_mints_and_allowances() {
+        let token = SimulationContract::new(
+            BaseContract::from(bindings::arbiter_token::ARBITERTOKEN_ABI.clone()),
+            bindings::arbiter_token::ARBITERTOKEN_BYTECODE
+                .clone()
+                .into_iter()
+                .collect(),
+        );
+        let recipient = B160::from_low_u64_be(1);
+        let spender = B160::from_low_u64_be(2);
+
+        let admin = TokenAdmin::new(token)
+            .mint(recipient, U256::from(100))
+            .approve(spender, U256::from(50));
+
+        assert_eq!(admin.mints, vec![(recipient, U256::from(100))]);
+        assert_eq!(admin.allowances, vec![(spender, U256::from(50))]);
+    }
+
+    fn deploy_token_pair_and_exchange(
+        manager: &mut SimulationManager,
+        initial_price: u64,
+    ) -> (SimulationContract, SimulationContract, SimulationContract) {
+        let arbiter_token = SimulationContract::new(
+            BaseContract::from(bindings::arbiter_token::ARBITERTOKEN_ABI.clone()),
+            bindings::arbiter_token::ARBITERTOKEN_BYTECODE
+                .clone()
+                .into_iter()
+                .collect(),
+        );
+        let token_x = manager.deploy(&arbiter_token, ("Token X".to_string(), "TKNX".to_string()));
+        let token_y = manager.deploy(&arbiter_token, ("Token Y".to_string(), "TKNY".to_string()));
+
+        let liquid_exchange = SimulationContract::new(
+            BaseContract::from(bindings::liquid_exchange::LIQUIDEXCHANGE_ABI.clone()),
+            bindings::liquid_exchange::LIQUIDEXCHANGE_BYTECODE
+                .clone()
+                .into_iter()
+                .collect(),
+        );
+        let exchange = manager.deploy(
+            &liquid_exchange,
+            (
+                recast_address(token_x.address.unwrap()),
+                recast_address(token_y.address.unwrap()),
+                U256::from(initial_price),
+            ),
+        );
+        (token_x, token_y, exchange)
+    }
+
+    #[tokio::test]
+    async fn test_pool_admin_startup_mints_deposit_to_exchange_and_updates_pool() {
+        let mut manager = SimulationManager::default();
+        let (token_x, token_y, exchange) = deploy_token_pair_and_exchange(&mut manager, 1000);
+
+        let rx = TokenAmount::from_human(0.0, 18);
+        let ry = TokenAmount::from_human(0.0, 18);
+        let concrete_pool = Arc::new(Mutex::new(ConstantProductPool::new(
+            recast_address(token_x.address.unwrap()),
+            recast_address(token_y.address.unwrap()),
+            rx,
+            ry,
+            0.003,
+        )));
+        // `PoolAdmin` only needs the type-erased `Cfmm` view; a concretely-typed clone of the same
+        // `Arc`/`Mutex` is kept around so the test can assert on `ConstantProductPool`-specific
+        // state (`shares_of`) that isn't part of the `Cfmm` trait.
+        let pool: Arc<Mutex<dyn Cfmm + Send>> = concrete_pool.clone();
+        let provider = Address::from_low_u64_be(1);
+        let dx = TokenAmount::from_human(10.0, 18);
+        let dy = TokenAmount::from_human(20.0, 18);
+
+        let mut admin = PoolAdmin::new(pool, token_x.clone(), token_y.clone(), exchange.clone())
+            .add_liquidity(provider, dx, dy);
+        assert_eq!(admin.deposits.len(), 1);
+
+        let client: ManagerClient = Arc::new(Mutex::new(manager));
+        admin.startup(client.clone()).await;
+        assert!(admin.deposits.is_empty());
+
+        // The deposit was actually minted to the exchange's on-chain balance, not just applied to
+        // the in-memory `pool`.
+        let mut manager = client.lock().await;
+        let call_data = token_x
+            .base_contract
+            .encode("balanceOf", recast_address(exchange.address.unwrap()))
+            .unwrap()
+            .into_iter()
+            .collect();
+        let execution_result = manager.call_contract(&token_x, call_data, Uint::from(0));
+        let value = manager.unpack_execution(execution_result);
+        let balance: U256 = token_x.base_contract.decode_output("balanceOf", value).unwrap();
+        assert_eq!(balance, dx.raw);
+        drop(manager);
+
+        assert!(concrete_pool.lock().await.shares_of(provider) > 0);
+    }
+
+    #[tokio::test]
+    async fn test_pool_admin_process_mirrors_swap_event_into_the_pool() {
+        let mut manager = SimulationManager::default();
+        let (token_x, token_y, exchange) = deploy_token_pair_and_exchange(&mut manager, 1000);
+
+        // `SimulationManager::call_contract` always transacts as the admin address, so mint and
+        // approve from the admin itself rather than a separate user.
+        let mint_amount = 20;
+        let call_data = token_x
+            .base_contract
+            .encode("mint", (recast_address(manager.address), U256::from(mint_amount)))
+            .unwrap()
+            .into_iter()
+            .collect();
+        manager.call_contract(&token_x, call_data, Uint::from(0));
+
+        // Let the exchange pull the input token from the admin during `swap`.
+        let call_data = token_x
+            .base_contract
+            .encode(
+                "increaseAllowance",
+                (recast_address(exchange.address.unwrap()), U256::from(mint_amount)),
+            )
+            .unwrap()
+            .into_iter()
+            .collect();
+        manager.call_contract(&token_x, call_data, Uint::from(0));
+
+        let swap_amount = mint_amount / 2;
+        let call_data = exchange
+            .base_contract
+            .encode(
+                "swap",
+                (recast_address(token_x.address.unwrap()), U256::from(swap_amount)),
+            )
+            .unwrap()
+            .into_iter()
+            .collect();
+        let execution_result = manager.call_contract(&exchange, call_data, Uint::from(0));
+        manager.unpack_execution(execution_result);
+        let event = manager.read_logs().remove(0);
+
+        // The pool needs non-empty reserves, or `ConstantProductPool::swap` rejects the replayed
+        // swap as draining its output reserve.
+        let rx = TokenAmount::from_human(100.0, 18);
+        let ry = TokenAmount::from_human(100.0, 18);
+        let concrete_pool = Arc::new(Mutex::new(ConstantProductPool::new(
+            recast_address(token_x.address.unwrap()),
+            recast_address(token_y.address.unwrap()),
+            rx,
+            ry,
+            0.0,
+        )));
+        let pool: Arc<Mutex<dyn Cfmm + Send>> = concrete_pool.clone();
+        let mut admin = PoolAdmin::new(pool, token_x, token_y, exchange);
+
+        let control_flow = admin.process(event).await;
+
+        assert_eq!(control_flow, ControlFlow::Continue(()));
+        // The decoded swap was replayed into the in-memory pool: its `x` reserve grew by the
+        // swap's input amount.
+        assert_eq!(concrete_pool.lock().await.rx.get().raw, rx.raw + U256::from(swap_amount));
+    }
+}
+
+/// Decodes the `SwapOccured` event emitted by `LiquidExchange`, as asserted against in
+/// `exchange::tests::test_swap_from_x_liquid_exchange`.
+pub fn decode_swap_occured(
+    exchange: &SimulationContract,
+    event: Event,
+) -> ethers::abi::Result<(Address, U256, Address, U256)> {
+    let log_topics = event
+        .topics
+        .into_iter()
+        .map(|topic| ethers::types::H256::from_slice(topic.as_slice()))
+        .collect();
+    let log_data = event.data.into();
+    exchange
+        .base_contract
+        .decode_event("SwapOccured", log_topics, log_data)
+}
@@ -0,0 +1,334 @@
+#![warn(missing_docs)]
+//! This module contains `ConcentratedLiquidityPool`, a Uniswap V3-style tick-based CFMM that
+//! sits alongside the simple `ConstantProductPool` so agent simulations can model routing and
+//! concentrated-liquidity arbitrage against a more realistic venue.
+//!
+//! Unlike `ConstantProductPool` (whose swap/liquidity math was rewritten to exact `U256` integer
+//! arithmetic), this pool still does its price/tick accounting in `f64`, converting `TokenAmount`s
+//! at the `Exchange`/`Cfmm` boundary via `to_human`/`from_human`. That is a deliberate, known
+//! limitation rather than an oversight: `sqrt_price_x96` here is a plain `f64` approximation of a
+//! Q64.96 fixed-point value, not an exact one, and porting the tick-crossing swap loop and
+//! [`liquidity_from_amounts`] to exact integer math (a real `U256`-based Q64.96 implementation)
+//! is a substantially larger rewrite than this module's test coverage currently justifies. Treat
+//! this pool as good for modeling tick-crossing *behavior*, not as a source of precise amounts.
+
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+
+use ethers::types::{Address, U256};
+
+use crate::exchange::{Cfmm, Exchange};
+use crate::token::TokenAmount;
+
+/// The lowest tick a position may be opened at (mirrors Uniswap V3's `MIN_TICK`).
+pub const MIN_TICK: i32 = -887272;
+/// The highest tick a position may be opened at (mirrors Uniswap V3's `MAX_TICK`).
+pub const MAX_TICK: i32 = 887272;
+
+/// The liquidity delta that activates or deactivates at a single initialized tick.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TickInfo {
+    /// The net change in active liquidity when price crosses this tick moving upward.
+    pub liquidity_net: i128,
+}
+
+/// A full-range LP position opened through the `Cfmm` trait: how much liquidity it minted, and
+/// the raw `x`/`y` amounts it was minted from, so [`ConcentratedLiquidityPool::remove_liquidity`] can pay out
+/// both legs proportionally to the liquidity burned rather than only returning `x`.
+#[derive(Debug, Clone, Copy, Default)]
+struct FullRangePosition {
+    liquidity: u128,
+    dx: u128,
+    dy: u128,
+}
+
+fn tick_to_sqrt_price(tick: i32) -> f64 {
+    1.0001f64.powf(tick as f64 / 2.0) * 2f64.powi(96)
+}
+
+/// Converts a deposit of `amount_x`/`amount_y` (human units) into the V3 liquidity `L` it mints
+/// over `[sqrt_lower, sqrt_upper)` at the pool's current `sqrt_price`, using the same
+/// piecewise formula Uniswap V3 uses to size a mint (`dx = L * (1/sqrt(Pa) - 1/sqrt(Pb))`,
+/// `dy = L * (sqrt(Pb) - sqrt(Pa))`, solved for `L` and taking the limiting side when the
+/// current price sits inside the range). `sqrt_lower`/`sqrt_upper`/`sqrt_price` are all in this
+/// module's `sqrt_price_x96` representation, so every product/difference of them is rescaled by
+/// `2^96` to cancel that shared factor back out.
+fn liquidity_from_amounts(
+    sqrt_price: f64,
+    sqrt_lower: f64,
+    sqrt_upper: f64,
+    amount_x: f64,
+    amount_y: f64,
+) -> u128 {
+    let q96 = 2f64.powi(96);
+    let liquidity = if sqrt_price <= sqrt_lower {
+        amount_x * sqrt_lower * sqrt_upper / (sqrt_upper - sqrt_lower) / q96
+    } else if sqrt_price >= sqrt_upper {
+        amount_y * q96 / (sqrt_upper - sqrt_lower)
+    } else {
+        let liquidity_x = amount_x * sqrt_price * sqrt_upper / (sqrt_upper - sqrt_price) / q96;
+        let liquidity_y = amount_y * q96 / (sqrt_price - sqrt_lower);
+        liquidity_x.min(liquidity_y)
+    };
+    liquidity.max(0.0) as u128
+}
+
+/// A concentrated-liquidity (Uniswap V3-style) CFMM, tracking price as `sqrt_price_x96`, the
+/// current `tick`, the `liquidity` active at that tick, and a sparse map of initialized ticks.
+pub struct ConcentratedLiquidityPool {
+    /// The pool's `x` token, used to determine a swap's direction in [`Exchange::swap`].
+    pub token_x: Address,
+    /// The pool's `y` token.
+    pub token_y: Address,
+    /// The current price, as `sqrt(price) * 2^96`.
+    pub sqrt_price_x96: Cell<f64>,
+    /// The tick corresponding to the current `sqrt_price_x96`.
+    pub tick: Cell<i32>,
+    /// The liquidity active at the current tick.
+    pub liquidity: Cell<u128>,
+    /// Initialized ticks, keyed by tick index, holding each tick's `liquidity_net`.
+    pub ticks: RefCell<BTreeMap<i32, TickInfo>>,
+    decimals_x: u8,
+    decimals_y: u8,
+    /// Full-range positions opened via the `Cfmm` trait, keyed by the depositing caller.
+    full_range_positions: RefCell<HashMap<Address, FullRangePosition>>,
+}
+
+impl ConcentratedLiquidityPool {
+    /// Creates a new pool at the given starting price/tick, with no liquidity yet deposited.
+    pub fn new(
+        token_x: Address,
+        token_y: Address,
+        sqrt_price_x96: f64,
+        tick: i32,
+        decimals_x: u8,
+        decimals_y: u8,
+    ) -> Self {
+        Self {
+            token_x,
+            token_y,
+            sqrt_price_x96: Cell::new(sqrt_price_x96),
+            tick: Cell::new(tick),
+            liquidity: Cell::new(0),
+            ticks: RefCell::new(BTreeMap::new()),
+            decimals_x,
+            decimals_y,
+            full_range_positions: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Adds `amount` of liquidity to the `[tick_lower, tick_upper)` range, updating
+    /// `liquidity_net` at both boundaries (positive at `tick_lower`, negative at `tick_upper`)
+    /// and, if the current tick already sits inside the range, adding `amount` to the pool's
+    /// active `liquidity` immediately.
+    ///
+    /// Named distinctly from `Cfmm::add_liquidity` (rather than overloading it) because an
+    /// inherent method and a trait method of the same name and arity on the same concrete type
+    /// would make the inherent one shadow the trait one at every dot-call site.
+    pub fn add_liquidity_range(&self, tick_lower: i32, tick_upper: i32, amount: u128) {
+        assert!(tick_lower < tick_upper, "tick_lower must be below tick_upper");
+
+        let mut ticks = self.ticks.borrow_mut();
+        ticks.entry(tick_lower).or_default().liquidity_net += amount as i128;
+        ticks.entry(tick_upper).or_default().liquidity_net -= amount as i128;
+        drop(ticks);
+
+        let tick = self.tick.get();
+        if tick_lower <= tick && tick < tick_upper {
+            self.liquidity.set(self.liquidity.get() + amount);
+        }
+    }
+}
+
+impl Exchange for ConcentratedLiquidityPool {
+    fn get_price(&self, _token_x: Address, _token_y: Address) -> f64 {
+        let q96 = 2f64.powi(96);
+        (self.sqrt_price_x96.get() / q96).powi(2)
+    }
+
+    fn swap(&self, token_in: Address, amount_in: TokenAmount) -> TokenAmount {
+        let zero_for_one = token_in == self.token_x;
+        let in_decimals = if zero_for_one {
+            self.decimals_x
+        } else {
+            self.decimals_y
+        };
+        let mut amount_remaining = amount_in.rescale(in_decimals).to_human();
+
+        // Step in plain `sqrt(P)` (not `sqrt(P) * 2^96`) so the formulas below use the same units
+        // as `liquidity_from_amounts`.
+        let q96 = 2f64.powi(96);
+        let mut sqrt_price = self.sqrt_price_x96.get() / q96;
+        let mut liquidity = self.liquidity.get() as f64;
+        let mut tick = self.tick.get();
+        let mut amount_out = 0.0;
+
+        let ticks = self.ticks.borrow();
+        while amount_remaining > 0.0 {
+            let next = if zero_for_one {
+                ticks.range(..tick).next_back()
+            } else {
+                ticks.range(tick + 1..).next()
+            };
+            let Some((&next_tick, info)) = next else {
+                // No more initialized liquidity in this direction.
+                break;
+            };
+            let sqrt_price_target = tick_to_sqrt_price(next_tick) / q96;
+
+            // No liquidity is active in this segment: there is nothing to trade against, so
+            // cross straight to the next tick without touching `amount_remaining`.
+            if liquidity == 0.0 {
+                sqrt_price = sqrt_price_target;
+                tick = next_tick;
+                liquidity = if zero_for_one {
+                    liquidity - info.liquidity_net as f64
+                } else {
+                    liquidity + info.liquidity_net as f64
+                };
+                continue;
+            }
+
+            let (consumed, produced, reached_target) = if zero_for_one {
+                // Δ(1/√P) = Δx / L
+                let max_dx = liquidity * (1.0 / sqrt_price_target - 1.0 / sqrt_price);
+                let dx = amount_remaining.min(max_dx);
+                let new_sqrt_price = 1.0 / (1.0 / sqrt_price + dx / liquidity);
+                let dy = liquidity * (sqrt_price - new_sqrt_price);
+                sqrt_price = new_sqrt_price;
+                (dx, dy, dx >= max_dx)
+            } else {
+                // Δ√P = Δy / L
+                let max_dy = liquidity * (sqrt_price_target - sqrt_price);
+                let dy = amount_remaining.min(max_dy);
+                let new_sqrt_price = sqrt_price + dy / liquidity;
+                let dx = liquidity * (1.0 / sqrt_price - 1.0 / new_sqrt_price);
+                sqrt_price = new_sqrt_price;
+                (dy, dx, dy >= max_dy)
+            };
+
+            amount_remaining -= consumed;
+            amount_out += produced;
+
+            if reached_target {
+                tick = next_tick;
+                liquidity = if zero_for_one {
+                    liquidity - info.liquidity_net as f64
+                } else {
+                    liquidity + info.liquidity_net as f64
+                };
+            } else {
+                break;
+            }
+        }
+        drop(ticks);
+
+        self.sqrt_price_x96.set(sqrt_price * q96);
+        self.tick.set(tick);
+        self.liquidity.set(liquidity as u128);
+
+        let out_decimals = if zero_for_one {
+            self.decimals_y
+        } else {
+            self.decimals_x
+        };
+        TokenAmount::from_human(amount_out, out_decimals)
+    }
+}
+
+impl Cfmm for ConcentratedLiquidityPool {
+    fn get_pools(&self) -> Vec<String> {
+        vec!["x/y (concentrated)".to_string()]
+    }
+
+    /// Opens a full-range `[MIN_TICK, MAX_TICK)` position on `caller`'s behalf, converting `dx`/`dy`
+    /// (human units) into the liquidity `L` they actually mint via [`liquidity_from_amounts`]
+    /// (`L` is in human units too, about `min(dx * sqrt(P), dy / sqrt(P))` for a full-range
+    /// position, truncated to a whole number, so it is many orders of magnitude smaller than the
+    /// raw `dx`/`dy` values — that is expected, not a bug). The returned shares, and
+    /// the tick/active-liquidity bookkeeping this updates, are denominated in that same `L`, not
+    /// in token units. Use [`ConcentratedLiquidityPool::add_liquidity_range`] directly to deposit
+    /// into a tighter range.
+    fn add_liquidity(&self, caller: Address, dx: TokenAmount, dy: TokenAmount) -> u128 {
+        let dx_amount = dx.rescale(self.decimals_x).to_human();
+        let dy_amount = dy.rescale(self.decimals_y).to_human();
+        let minted = liquidity_from_amounts(
+            self.sqrt_price_x96.get(),
+            tick_to_sqrt_price(MIN_TICK),
+            tick_to_sqrt_price(MAX_TICK),
+            dx_amount,
+            dy_amount,
+        );
+        self.add_liquidity_range(MIN_TICK, MAX_TICK, minted);
+
+        let mut positions = self.full_range_positions.borrow_mut();
+        let position = positions.entry(caller).or_default();
+        position.liquidity += minted;
+        position.dx += dx.rescale(self.decimals_x).raw.as_u128();
+        position.dy += dy.rescale(self.decimals_y).raw.as_u128();
+        minted
+    }
+
+    /// Burns `shares` of liquidity (as returned by [`Self::add_liquidity`]) from `caller`'s
+    /// full-range position, paying out `dx`/`dy` proportionally to the fraction of the position's
+    /// liquidity burned.
+    fn remove_liquidity(&self, caller: Address, shares: u128) -> (TokenAmount, TokenAmount) {
+        let mut positions = self.full_range_positions.borrow_mut();
+        let position = positions
+            .get_mut(&caller)
+            .expect("caller has no full-range position");
+        assert!(
+            position.liquidity >= shares,
+            "caller does not hold enough liquidity"
+        );
+
+        let dx_out = (U256::from(position.dx) * U256::from(shares)
+            / U256::from(position.liquidity))
+        .as_u128();
+        let dy_out = (U256::from(position.dy) * U256::from(shares)
+            / U256::from(position.liquidity))
+        .as_u128();
+        position.liquidity -= shares;
+        position.dx -= dx_out;
+        position.dy -= dy_out;
+
+        let mut ticks = self.ticks.borrow_mut();
+        ticks.entry(MIN_TICK).or_default().liquidity_net -= shares as i128;
+        ticks.entry(MAX_TICK).or_default().liquidity_net += shares as i128;
+        drop(ticks);
+        self.liquidity.set(self.liquidity.get() - shares);
+
+        (
+            TokenAmount::new(U256::from(dx_out), self.decimals_x),
+            TokenAmount::new(U256::from(dy_out), self.decimals_y),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_swap_crosses_multiple_ticks() {
+        let token_x = Address::from_low_u64_be(1);
+        let token_y = Address::from_low_u64_be(2);
+        let pool = ConcentratedLiquidityPool::new(token_x, token_y, tick_to_sqrt_price(0), 0, 18, 18);
+
+        // Two adjacent ranges: [-100, 100) is active at the starting tick, [-200, -100) only
+        // becomes active once the swap below pushes the price past -100.
+        pool.add_liquidity_range(-100, 100, 1_000);
+        pool.add_liquidity_range(-200, -100, 2_000);
+        assert_eq!(pool.liquidity.get(), 1_000);
+
+        // Selling token_x pushes the price (and tick) down, crossing out of the first range at
+        // -100 and into the second, then exhausting it by -200, the lowest initialized tick.
+        // Crossing to -100 takes about 5.01 x at L = 1_000 and on to -200 about 10.07 more at
+        // L = 2_000, so 20 x crosses both.
+        let amount_in = TokenAmount::from_human(20.0, 18);
+        pool.swap(token_x, amount_in);
+
+        assert_eq!(pool.tick.get(), -200);
+        assert_eq!(pool.liquidity.get(), 0);
+    }
+}
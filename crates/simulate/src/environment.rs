@@ -0,0 +1,238 @@
+#![warn(missing_docs)]
+//! This module contains the `SimulationManager`, the entry point that owns the in-memory EVM
+//! that contracts are deployed to and called against, along with the `SimulationContract`
+//! wrapper that pairs a contract's ABI with its bytecode.
+
+use std::collections::HashMap;
+
+use ethers::{
+    prelude::BaseContract,
+    providers::{Http, Provider},
+    types::{Address, Bytes as EthersBytes, U256},
+};
+use revm::{
+    db::{CacheDB, EmptyDB, EthersDB},
+    primitives::{CreateScheme, ExecutionResult, Log, Output, TransactTo, B160, U256 as rU256},
+    EVM,
+};
+
+use crate::agent::Agent;
+
+/// Converts a revm `B160` address into the `ethers` `Address` type used by contract bindings.
+pub fn recast_address(address: B160) -> Address {
+    Address::from(address.as_bytes())
+}
+
+/// Pairs a contract's ABI with its deployment bytecode, plus the address it was deployed to (once
+/// known).
+#[derive(Debug, Clone)]
+pub struct SimulationContract {
+    /// The ABI-aware contract used to encode calls and decode outputs/events.
+    pub base_contract: BaseContract,
+    /// The contract's deployment bytecode.
+    pub bytecode: revm::primitives::Bytes,
+    /// The address this contract was deployed to, set once `SimulationManager::deploy` runs.
+    pub address: Option<B160>,
+}
+
+impl SimulationContract {
+    /// Creates a new, not-yet-deployed contract from its ABI and bytecode.
+    pub fn new(base_contract: BaseContract, bytecode: revm::primitives::Bytes) -> Self {
+        Self {
+            base_contract,
+            bytecode,
+            address: None,
+        }
+    }
+}
+
+/// A `SimulationManager` backed by the default, purely in-memory database — the common case, and
+/// the type `Behavior` clients are built around.
+pub type EmptyDbManager = SimulationManager<EmptyDB>;
+
+/// Owns the EVM that a simulation runs against and the admin account that deploys contracts.
+///
+/// By default the EVM is backed by an empty, purely in-memory database. Use
+/// [`SimulationManager::fork`] instead of [`SimulationManager::default`] to seed that database
+/// lazily from a real chain via JSON-RPC.
+pub struct SimulationManager<DB: revm::db::DatabaseRef = EmptyDB> {
+    /// The underlying revm EVM that all deploys and calls run against.
+    pub evm: EVM<CacheDB<DB>>,
+    /// The admin/deployer address that owns the EVM's nonce.
+    pub address: B160,
+    /// Non-admin accounts that have been created in this simulation.
+    pub agents: HashMap<B160, Agent>,
+    /// Decimals recorded per deployed token contract, consulted when building a `TokenAmount` so
+    /// swap math and price computation scale each leg of a pair correctly.
+    pub decimals: HashMap<B160, u8>,
+}
+
+impl Default for SimulationManager<EmptyDB> {
+    fn default() -> Self {
+        let mut evm = EVM::new();
+        evm.database(CacheDB::new(EmptyDB {}));
+        let address = B160::from_low_u64_be(0);
+        let mut manager = Self {
+            evm,
+            address,
+            agents: HashMap::new(),
+            decimals: HashMap::new(),
+        };
+        manager.fund(address);
+        manager
+    }
+}
+
+impl<DB: revm::db::DatabaseRef> SimulationManager<DB> {
+    /// Credits `address` with a large ether balance so it can pay for deploys and calls.
+    fn fund(&mut self, address: B160) {
+        let db = self.evm.db.as_mut().expect("database must be set");
+        let mut info = db.basic(address).unwrap_or_default().unwrap_or_default();
+        info.balance = rU256::MAX >> 1;
+        db.insert_account_info(address, info);
+    }
+
+    /// Registers a new user account and funds it so it can send transactions.
+    pub fn create_user(&mut self, address: B160) {
+        self.fund(address);
+        self.agents.insert(address, Agent::new(address));
+    }
+
+    /// Deploys `contract`'s bytecode (ABI-encoding `constructor_args` as the deployment calldata
+    /// suffix) from the admin address, returning the contract with its deployed `address` set.
+    pub fn deploy<T: ethers::abi::Tokenize>(
+        &mut self,
+        contract: &SimulationContract,
+        constructor_args: T,
+    ) -> SimulationContract {
+        let mut bytecode = contract.bytecode.to_vec();
+        if let Ok(encoded) = contract.base_contract.encode_constructor(constructor_args) {
+            bytecode.extend(encoded.into_iter());
+        }
+
+        self.evm.env.tx.caller = self.address;
+        self.evm.env.tx.transact_to = TransactTo::Create(CreateScheme::Create);
+        self.evm.env.tx.data = bytecode.into();
+        self.evm.env.tx.value = rU256::ZERO;
+
+        let result = self.evm.transact_commit().expect("deploy transaction failed");
+        let address = match result {
+            ExecutionResult::Success {
+                output: Output::Create(_, Some(address)),
+                ..
+            } => address,
+            other => panic!("deploy did not create a contract: {other:?}"),
+        };
+
+        let mut deployed = contract.clone();
+        deployed.address = Some(address);
+        deployed
+    }
+
+    /// Sends `call_data` as a transaction to `contract` from the admin address with `value` wei
+    /// attached, committing the resulting state changes.
+    pub fn call_contract(
+        &mut self,
+        contract: &SimulationContract,
+        call_data: revm::primitives::Bytes,
+        value: rU256,
+    ) -> ExecutionResult {
+        self.evm.env.tx.caller = self.address;
+        self.evm.env.tx.transact_to =
+            TransactTo::Call(contract.address.expect("contract has not been deployed"));
+        self.evm.env.tx.data = call_data;
+        self.evm.env.tx.value = value;
+
+        self.evm
+            .transact_commit()
+            .expect("call transaction failed")
+    }
+
+    /// Extracts the raw return data from a successful `ExecutionResult`, panicking otherwise.
+    pub fn unpack_execution(&self, result: ExecutionResult) -> ethers::types::Bytes {
+        match result {
+            ExecutionResult::Success {
+                output: Output::Call(bytes),
+                ..
+            } => EthersBytes::from(bytes.to_vec()),
+            other => panic!("call did not succeed: {other:?}"),
+        }
+    }
+
+    /// Returns the logs emitted by the most recently committed transaction.
+    pub fn read_logs(&self) -> Vec<Log> {
+        self.evm.db.as_ref().expect("database must be set").logs()
+    }
+
+    /// Records `token`'s decimals so future `TokenAmount`s built for it scale correctly.
+    pub fn record_decimals(&mut self, token: B160, decimals: u8) {
+        self.decimals.insert(token, decimals);
+    }
+
+    /// Wraps a raw on-chain value as a `TokenAmount`, scaled by `token`'s recorded decimals (or
+    /// 18, the common default, if none were recorded).
+    pub fn token_amount(&self, token: B160, raw: U256) -> crate::token::TokenAmount {
+        let decimals = *self.decimals.get(&token).unwrap_or(&18);
+        crate::token::TokenAmount::new(raw, decimals)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_create_user_is_funded_and_registered() {
+        let mut manager = SimulationManager::default();
+        let user_address = B160::from_low_u64_be(1);
+        assert!(!manager.agents.contains_key(&user_address));
+
+        manager.create_user(user_address);
+
+        assert!(manager.agents.contains_key(&user_address));
+        let db = manager.evm.db.as_ref().expect("database must be set");
+        let info = db
+            .basic(user_address)
+            .expect("account lookup should not fail")
+            .expect("created user should have an account");
+        assert_eq!(info.balance, rU256::MAX >> 1);
+    }
+
+    #[test]
+    fn test_token_amount_uses_recorded_decimals() {
+        let mut manager = SimulationManager::default();
+        let token = B160::from_low_u64_be(2);
+
+        // Falls back to 18 decimals (the common default) before any are recorded.
+        let amount = manager.token_amount(token, U256::from(1));
+        assert_eq!(amount.decimals, 18);
+
+        manager.record_decimals(token, 6);
+        let amount = manager.token_amount(token, U256::from(1_000_000));
+        assert_eq!(amount.decimals, 6);
+        assert_eq!(amount.to_human(), 1.0);
+    }
+}
+
+impl SimulationManager<EthersDB<Provider<Http>>> {
+    /// Builds a `SimulationManager` whose database is backed by a live JSON-RPC `provider`,
+    /// pinned to `block_number`. Any account or storage slot missing from the local `CacheDB` is
+    /// fetched lazily from the remote node the first time it is touched by a deploy or call, so
+    /// a simulation can be seeded with the real reserves/balances/allowances of a deployed pool
+    /// instead of synthetic ones.
+    pub fn fork(provider: Provider<Http>, block_number: U256) -> Self {
+        let ethers_db = EthersDB::new(std::sync::Arc::new(provider), Some(block_number.into()))
+            .expect("failed to construct EthersDB");
+        let mut evm = EVM::new();
+        evm.database(CacheDB::new(ethers_db));
+        let address = B160::from_low_u64_be(0);
+        let mut manager = Self {
+            evm,
+            address,
+            agents: HashMap::new(),
+            decimals: HashMap::new(),
+        };
+        manager.fund(address);
+        manager
+    }
+}
@@ -1,28 +1,221 @@
 #![warn(missing_docs)]
 //! This module contains the `Exchange` and `Cfmm` traits that are used to describe the functionality of a contract that can be used to swap tokens.
 
-use ethers::types::Address;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+use ethers::types::{Address, U256};
+
+use crate::token::TokenAmount;
+
+/// Integer square root via Newton's method, used to mint the first LP deposit's shares without
+/// round-tripping reserve amounts through `f64`.
+fn isqrt(value: U256) -> U256 {
+    if value.is_zero() {
+        return U256::zero();
+    }
+    let mut x = value;
+    let mut y = (x + U256::one()) / 2;
+    while y < x {
+        x = y;
+        y = (x + value / x) / 2;
+    }
+    x
+}
 
 /// A trait that describes the functionality of any exchange.
 pub trait Exchange {
     /// Returns the price listed on the exchange for a pair.
     fn get_price(&self, token_x: Address, token_y: Address) -> f64;
-    /// Swaps a token for another token using the exchange's logic.
-    fn swap(&self, token_in: Address, amount: f64);
+    /// Swaps a token for another token using the exchange's logic, returning the amount received.
+    fn swap(&self, token_in: Address, amount: TokenAmount) -> TokenAmount;
 }
 
 /// Trait that uses the `Exchange` trait to describe the more detailed functionality of a CFMM.
 pub trait Cfmm: Exchange {
     /// Returns the list of pools that the CFMM supports.
     fn get_pools(&self) -> Vec<String>;
-    /// Lets a user add liquidity to a pool.
-    fn add_liquidity(&self, token: &str, amount: f64);
-    /// Lets a user remove liquidity from a pool.
-    fn remove_liquidity(&self, token: &str, amount: f64);
+    /// Lets `caller` add `dx` of token `x` and `dy` of token `y` to a pool, minting LP shares.
+    fn add_liquidity(&self, caller: Address, dx: TokenAmount, dy: TokenAmount) -> u128;
+    /// Burns `shares` from `caller`'s balance and returns the underlying `(dx, dy)` withdrawn.
+    fn remove_liquidity(&self, caller: Address, shares: u128) -> (TokenAmount, TokenAmount);
+}
+
+/// A constant-product (`x * y = k`) automated market maker, used as an in-memory reference
+/// implementation to validate agent behavior against the deployed `LiquidExchange` contract.
+pub struct ConstantProductPool {
+    /// The pool's `x` token, used to determine a swap's direction in [`Exchange::swap`].
+    pub token_x: Address,
+    /// The pool's `y` token.
+    pub token_y: Address,
+    /// Reserve of token `x` held by the pool.
+    pub rx: Cell<TokenAmount>,
+    /// Reserve of token `y` held by the pool.
+    pub ry: Cell<TokenAmount>,
+    /// Fee fraction charged on the input amount of every swap (e.g. `0.003` for 0.3%).
+    pub fee: f64,
+    /// Total number of outstanding LP shares.
+    total_shares: Cell<u128>,
+    /// LP share balance held by each liquidity provider.
+    shares: RefCell<HashMap<Address, u128>>,
+}
+
+impl ConstantProductPool {
+    /// Creates a new pool seeded with the given reserves and fee fraction.
+    pub fn new(token_x: Address, token_y: Address, rx: TokenAmount, ry: TokenAmount, fee: f64) -> Self {
+        Self {
+            token_x,
+            token_y,
+            rx: Cell::new(rx),
+            ry: Cell::new(ry),
+            fee,
+            total_shares: Cell::new(0),
+            shares: RefCell::new(HashMap::new()),
+        }
+    }
+
+    /// Returns the LP share balance held by `provider`.
+    pub fn shares_of(&self, provider: Address) -> u128 {
+        *self.shares.borrow().get(&provider).unwrap_or(&0)
+    }
+}
+
+impl ConstantProductPool {
+    /// The pool's fee fraction expressed in integer basis points (out of `10_000`), so swap and
+    /// liquidity math can stay in exact integer arithmetic on the reserves' raw units instead of
+    /// round-tripping through `f64`.
+    fn fee_bps(&self) -> U256 {
+        U256::from((self.fee * 10_000.0).round() as u64)
+    }
+}
+
+impl Exchange for ConstantProductPool {
+    fn get_price(&self, token_x: Address, _token_y: Address) -> f64 {
+        // The ratio is just for display, so normalizing decimals and converting to `f64` here
+        // (rather than in the reserve/swap accounting below) doesn't reintroduce mis-scaling.
+        let rx = self.rx.get();
+        let ry = self.ry.get();
+        let decimals = rx.decimals.max(ry.decimals);
+        let rx_n = rx.rescale(decimals).to_human();
+        let ry_n = ry.rescale(decimals).to_human();
+        if token_x == self.token_x {
+            ry_n / rx_n * (1.0 - self.fee)
+        } else {
+            rx_n / ry_n * (1.0 - self.fee)
+        }
+    }
+
+    fn swap(&self, token_in: Address, amount_in: TokenAmount) -> TokenAmount {
+        let zero_for_one = token_in == self.token_x;
+        assert!(
+            zero_for_one || token_in == self.token_y,
+            "swap: token_in is neither the pool's `x` nor `y` token"
+        );
+
+        let (reserve_in, reserve_out) = if zero_for_one {
+            (self.rx.get(), self.ry.get())
+        } else {
+            (self.ry.get(), self.rx.get())
+        };
+        let invariant_before = reserve_in
+            .raw
+            .checked_mul(reserve_out.raw)
+            .expect("x * y invariant overflowed U256");
+
+        let amount_in_raw = amount_in.rescale(reserve_in.decimals).raw;
+        let amount_in_with_fee = amount_in_raw * (U256::from(10_000) - self.fee_bps()) / 10_000;
+        let amount_out_raw =
+            reserve_out.raw * amount_in_with_fee / (reserve_in.raw + amount_in_with_fee);
+        assert!(
+            amount_out_raw < reserve_out.raw,
+            "swap would drain the output reserve"
+        );
+
+        let reserve_in_after = reserve_in.raw + amount_in_raw;
+        let reserve_out_after = reserve_out.raw - amount_out_raw;
+        assert!(
+            reserve_in_after
+                .checked_mul(reserve_out_after)
+                .expect("x * y invariant overflowed U256")
+                >= invariant_before,
+            "swap would decrease the `x * y` invariant"
+        );
+
+        if zero_for_one {
+            self.rx
+                .set(TokenAmount::new(reserve_in_after, reserve_in.decimals));
+            self.ry
+                .set(TokenAmount::new(reserve_out_after, reserve_out.decimals));
+        } else {
+            self.ry
+                .set(TokenAmount::new(reserve_in_after, reserve_in.decimals));
+            self.rx
+                .set(TokenAmount::new(reserve_out_after, reserve_out.decimals));
+        }
+        TokenAmount::new(amount_out_raw, reserve_out.decimals)
+    }
+}
+
+impl Cfmm for ConstantProductPool {
+    fn get_pools(&self) -> Vec<String> {
+        vec!["x/y".to_string()]
+    }
+
+    fn add_liquidity(&self, caller: Address, dx: TokenAmount, dy: TokenAmount) -> u128 {
+        let rx = self.rx.get();
+        let ry = self.ry.get();
+        let dx_raw = dx.rescale(rx.decimals).raw;
+        let total_shares = self.total_shares.get();
+
+        let (dx_raw, dy_raw, minted) = if total_shares == 0 {
+            let dy_raw = dy.rescale(ry.decimals).raw;
+            let minted = isqrt(dx_raw * dy_raw).as_u128();
+            (dx_raw, dy_raw, minted)
+        } else {
+            // Deposit the ratio-matching amount of `y` for the `x` the caller supplied, so the
+            // pool's price is left unchanged by the deposit.
+            let dy_raw = dx_raw * ry.raw / rx.raw;
+            let total_shares_raw = U256::from(total_shares);
+            let minted_x = dx_raw * total_shares_raw / rx.raw;
+            let minted_y = dy_raw * total_shares_raw / ry.raw;
+            let minted = minted_x.min(minted_y).as_u128();
+            (dx_raw, dy_raw, minted)
+        };
+
+        self.rx.set(TokenAmount::new(rx.raw + dx_raw, rx.decimals));
+        self.ry.set(TokenAmount::new(ry.raw + dy_raw, ry.decimals));
+        self.total_shares.set(total_shares + minted);
+        *self.shares.borrow_mut().entry(caller).or_insert(0) += minted;
+        minted
+    }
+
+    fn remove_liquidity(&self, caller: Address, shares: u128) -> (TokenAmount, TokenAmount) {
+        let mut balances = self.shares.borrow_mut();
+        let balance = balances.entry(caller).or_insert(0);
+        assert!(*balance >= shares, "caller does not hold enough LP shares");
+
+        let total_shares = self.total_shares.get();
+        let rx = self.rx.get();
+        let ry = self.ry.get();
+        let shares_raw = U256::from(shares);
+        let total_shares_raw = U256::from(total_shares);
+        let dx_raw = shares_raw * rx.raw / total_shares_raw;
+        let dy_raw = shares_raw * ry.raw / total_shares_raw;
+
+        *balance -= shares;
+        self.total_shares.set(total_shares - shares);
+        self.rx.set(TokenAmount::new(rx.raw - dx_raw, rx.decimals));
+        self.ry.set(TokenAmount::new(ry.raw - dy_raw, ry.decimals));
+        (
+            TokenAmount::new(dx_raw, rx.decimals),
+            TokenAmount::new(dy_raw, ry.decimals),
+        )
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::{ConstantProductPool, TokenAmount};
     use bindings;
     use ethers::{
         prelude::{BaseContract, H256, U256},
@@ -34,6 +227,73 @@ mod tests {
         agent::Agent,
         environment::{recast_address, SimulationContract, SimulationManager},
     };
+
+    #[test]
+    fn test_constant_product_swap_maintains_invariant() {
+        let token_x = Address::from_low_u64_be(1);
+        let token_y = Address::from_low_u64_be(2);
+        let rx = TokenAmount::from_human(100.0, 18);
+        let ry = TokenAmount::from_human(100.0, 18);
+        let pool = ConstantProductPool::new(token_x, token_y, rx, ry, 0.003);
+        let invariant_before = rx.raw * ry.raw;
+
+        let amount_in = TokenAmount::from_human(10.0, 18);
+        let amount_out = pool.swap(token_x, amount_in);
+
+        assert!(amount_out.raw > U256::zero());
+        let invariant_after = pool.rx.get().raw * pool.ry.get().raw;
+        assert!(
+            invariant_after >= invariant_before,
+            "swap fee should leave the `x * y` invariant unchanged or growing"
+        );
+    }
+
+    #[test]
+    fn test_constant_product_swap_direction_depends_on_token_in() {
+        // Selling `y` (the second constructor argument) must be priced against the `y` reserve,
+        // not silently treated as a sale of `x` just because `x` is always consumed first in the
+        // formula.
+        let token_x = Address::from_low_u64_be(1);
+        let token_y = Address::from_low_u64_be(2);
+        let rx = TokenAmount::from_human(100.0, 18);
+        let ry = TokenAmount::from_human(100.0, 6);
+        let pool = ConstantProductPool::new(token_x, token_y, rx, ry, 0.0);
+
+        let amount_in = TokenAmount::from_human(10.0, 6);
+        let amount_out = pool.swap(token_y, amount_in);
+
+        // The `y` reserve (6 decimals) grew by the input and the `x` reserve (18 decimals) shrank,
+        // not the other way around.
+        assert_eq!(pool.ry.get().raw, ry.raw + amount_in.raw);
+        assert!(pool.rx.get().raw < rx.raw);
+        assert_eq!(amount_out.decimals, 18);
+    }
+
+    #[test]
+    fn test_add_and_remove_liquidity_round_trip() {
+        // Start with no reserves, so the first deposit is the pool's only LP position and a full
+        // withdrawal of its shares must return exactly what was deposited.
+        let token_x = Address::from_low_u64_be(1);
+        let token_y = Address::from_low_u64_be(2);
+        let rx = TokenAmount::from_human(0.0, 18);
+        let ry = TokenAmount::from_human(0.0, 18);
+        let pool = ConstantProductPool::new(token_x, token_y, rx, ry, 0.003);
+        let provider = Address::from_low_u64_be(1);
+
+        let dx = TokenAmount::from_human(10.0, 18);
+        let dy = TokenAmount::from_human(20.0, 18);
+        let minted = pool.add_liquidity(provider, dx, dy);
+        assert!(minted > 0);
+        assert_eq!(pool.shares_of(provider), minted);
+
+        let (dx_out, dy_out) = pool.remove_liquidity(provider, minted);
+        assert_eq!(pool.shares_of(provider), 0);
+        assert_eq!(pool.rx.get().raw, U256::zero());
+        assert_eq!(pool.ry.get().raw, U256::zero());
+        assert_eq!(dx_out.raw, dx.raw);
+        assert_eq!(dy_out.raw, dy.raw);
+    }
+
     #[test]
     fn test_swap_from_x_liquid_exchange() {
         // Set up the execution manager and a user address.
@@ -0,0 +1,129 @@
+#![warn(missing_docs)]
+//! This module contains `TokenAmount`, a decimals-aware fixed-point amount that replaces bare
+//! `f64`/`U256` values at the `Exchange`/`Cfmm` trait boundary so that tokens with different
+//! decimals (e.g. a 6-decimal stablecoin against an 18-decimal asset) never get silently
+//! mis-scaled against each other.
+//!
+//! `raw`/`decimals` is the source of truth and [`TokenAmount::rescale`] moves between precisions
+//! with exact integer multiplication/division, the same way a token contract would. `from_human`
+//! and `to_human` exist only to bridge a handful of human-readable numbers (test fixtures, prices
+//! for display) in and out of that representation; pool accounting (`ConstantProductPool`) never
+//! round-trips through them.
+
+use ethers::types::U256;
+
+/// A token amount carrying its raw on-chain value alongside the token's `decimals`, so it can be
+/// converted to and from human-readable units without the caller having to track scaling itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenAmount {
+    /// The raw, integer on-chain value (e.g. wei for an 18-decimal token).
+    pub raw: U256,
+    /// The number of decimals the raw value is scaled by.
+    pub decimals: u8,
+}
+
+impl TokenAmount {
+    /// Wraps a raw on-chain value with its token's decimals.
+    pub fn new(raw: U256, decimals: u8) -> Self {
+        Self { raw, decimals }
+    }
+
+    /// Builds a `TokenAmount` from a human-readable amount (e.g. `1.5` tokens).
+    ///
+    /// This is meant for human-authored values (test fixtures, config), not for results of pool
+    /// math. Panics rather than silently saturating if `amount` is negative, non-finite, or too
+    /// large to represent.
+    pub fn from_human(amount: f64, decimals: u8) -> Self {
+        assert!(
+            amount.is_finite() && amount >= 0.0,
+            "TokenAmount::from_human: amount must be a finite, non-negative number"
+        );
+        let scale = 10f64.powi(decimals as i32);
+        let scaled = amount * scale;
+        assert!(
+            scaled < u128::MAX as f64,
+            "TokenAmount::from_human: amount does not fit in a TokenAmount at {decimals} decimals"
+        );
+        Self::new(U256::from(scaled.round() as u128), decimals)
+    }
+
+    /// Converts the raw value back to a human-readable amount, for display/price purposes only.
+    ///
+    /// Unlike `U256::as_u128`, this never panics: values beyond `f64`'s 53 bits of mantissa
+    /// precision are rounded rather than rejected, exactly as any other lossy integer-to-float
+    /// conversion would be.
+    pub fn to_human(&self) -> f64 {
+        let scale = 10f64.powi(self.decimals as i32);
+        u256_to_f64(self.raw) / scale
+    }
+
+    /// Re-expresses this amount at a different decimals precision using exact integer
+    /// multiplication/division (the same scaling a token contract applies), not a round-trip
+    /// through `f64`. Reducing precision truncates any remainder, matching on-chain rounding.
+    pub fn rescale(&self, decimals: u8) -> Self {
+        if decimals == self.decimals {
+            return *self;
+        }
+        if decimals > self.decimals {
+            let factor = U256::from(10u64).pow(U256::from(decimals - self.decimals));
+            Self::new(
+                self.raw
+                    .checked_mul(factor)
+                    .expect("TokenAmount::rescale: raw value overflowed U256"),
+                decimals,
+            )
+        } else {
+            let factor = U256::from(10u64).pow(U256::from(self.decimals - decimals));
+            Self::new(self.raw / factor, decimals)
+        }
+    }
+}
+
+/// Converts a `U256` to `f64` without panicking, regardless of magnitude (unlike
+/// `U256::as_u128`, which panics above `u128::MAX`).
+fn u256_to_f64(value: U256) -> f64 {
+    let mut result = 0f64;
+    for limb in value.0.iter().rev() {
+        result = result * 18_446_744_073_709_551_616.0 /* 2^64 */ + *limb as f64;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_human_to_human_round_trip() {
+        let amount = TokenAmount::from_human(1.5, 18);
+        assert_eq!(amount.raw, U256::from(1_500_000_000_000_000_000u64));
+        assert_eq!(amount.to_human(), 1.5);
+    }
+
+    #[test]
+    fn test_rescale_is_exact_integer_scaling() {
+        // 1 unit of a 6-decimal token rescaled up to 18 decimals, and back down, round-trips
+        // exactly since 18 > 6 loses no precision going up and the trailing zeros divide out
+        // cleanly coming back down.
+        let amount = TokenAmount::new(U256::from(1_000_000u64), 6);
+        let rescaled = amount.rescale(18);
+        assert_eq!(rescaled.raw, U256::from(1_000_000_000_000_000_000u64));
+        assert_eq!(rescaled.rescale(6).raw, amount.raw);
+    }
+
+    #[test]
+    fn test_rescale_down_truncates_like_a_token_contract() {
+        // Scaling down from 18 to 6 decimals truncates the remainder rather than rounding.
+        // 1_999_999_999_999 at 18 decimals is 1.999999999999 units at 6 decimals: rounding would
+        // give 2, truncation gives 1.
+        let amount = TokenAmount::new(U256::from(1_999_999_999_999u64), 18);
+        let rescaled = amount.rescale(6);
+        assert_eq!(rescaled.raw, U256::from(1u64));
+    }
+
+    #[test]
+    #[should_panic(expected = "finite, non-negative")]
+    fn test_from_human_rejects_negative_amounts() {
+        TokenAmount::from_human(-1.0, 18);
+    }
+}